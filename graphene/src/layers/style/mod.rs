@@ -3,6 +3,7 @@
 use crate::color::Color;
 use crate::consts::{LAYER_OUTLINE_STROKE_COLOR, LAYER_OUTLINE_STROKE_WEIGHT};
 
+use base64::Engine;
 use glam::{DAffine2, DVec2};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Write};
@@ -40,6 +41,8 @@ impl Default for ViewMode {
 pub enum GradientType {
 	Linear,
 	Radial,
+	/// A sweep (angular) gradient that revolves around a center point, like those found in raqote and Vello.
+	Conic,
 }
 
 impl Default for GradientType {
@@ -48,30 +51,100 @@ impl Default for GradientType {
 	}
 }
 
+/// The method used to extend a gradient's colors beyond its `start`/`end` endpoints, matching the SVG `spreadMethod` attribute.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Serialize, Deserialize)]
+pub enum SpreadMethod {
+	Pad,
+	Reflect,
+	Repeat,
+}
+
+impl Default for SpreadMethod {
+	fn default() -> Self {
+		SpreadMethod::Pad
+	}
+}
+
+impl Display for SpreadMethod {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			SpreadMethod::Pad => "pad",
+			SpreadMethod::Reflect => "reflect",
+			SpreadMethod::Repeat => "repeat",
+		})
+	}
+}
+
+/// The positional geometry of a [Gradient], kept distinct from its [GradientType] so that radial gradients can express
+/// an off-center focal point, mirroring pathfinder's split of linear vs. radial gradient geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GradientGeometry {
+	Linear {
+		start: DVec2,
+		end: DVec2,
+	},
+	Radial {
+		center: DVec2,
+		radius: f64,
+		/// The focal point, which may be offset from `center` to produce an off-center highlight. Defaults to `center`.
+		focus: DVec2,
+		/// The radius of the focal point. Defaults to 0, matching SVG's `fr`.
+		focus_radius: f64,
+	},
+	Conic {
+		center: DVec2,
+		start_angle: f64,
+		end_angle: f64,
+	},
+}
+
+impl Default for GradientGeometry {
+	fn default() -> Self {
+		GradientGeometry::Linear { start: DVec2::ZERO, end: DVec2::ZERO }
+	}
+}
+
 /// A gradient fill.
 ///
-/// Contains the start and end points, along with the colors at varying points along the length.
+/// Contains the gradient's [GradientGeometry], along with the colors at varying points along its length.
 #[repr(C)]
-#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
 pub struct Gradient {
-	pub start: DVec2,
-	pub end: DVec2,
+	pub geometry: GradientGeometry,
+	/// Pre-dates `GradientGeometry`; `render_defs` instead derives the gradient's on-canvas placement entirely from
+	/// `multiplied_transform`/`bounds`, so this field is not folded in there. Kept for callers that still read/write it.
 	pub transform: DAffine2,
 	pub positions: Vec<(f64, Option<Color>)>,
 	uuid: u64,
 	pub gradient_type: GradientType,
+	pub spread: SpreadMethod,
 }
 
 impl Gradient {
 	/// Constructs a new gradient with the colors at 0 and 1 specified.
 	pub fn new(start: DVec2, start_color: Color, end: DVec2, end_color: Color, transform: DAffine2, uuid: u64, gradient_type: GradientType) -> Self {
+		let geometry = match gradient_type {
+			GradientType::Linear => GradientGeometry::Linear { start, end },
+			GradientType::Radial => GradientGeometry::Radial {
+				center: start,
+				radius: (end - start).length(),
+				focus: start,
+				focus_radius: 0.,
+			},
+			GradientType::Conic => GradientGeometry::Conic {
+				center: start,
+				start_angle: 0.,
+				end_angle: std::f64::consts::TAU,
+			},
+		};
+
 		Gradient {
-			start,
-			end,
+			geometry,
 			positions: vec![(0., Some(start_color)), (1., Some(end_color))],
 			transform,
 			uuid,
 			gradient_type,
+			spread: SpreadMethod::default(),
 		}
 	}
 
@@ -91,9 +164,6 @@ impl Gradient {
 		let mod_gradient = transformed_bound_transform.inverse();
 		let mod_points = mod_gradient.inverse() * transformed_bound_transform.inverse() * updated_transform;
 
-		let start = mod_points.transform_point2(self.start);
-		let end = mod_points.transform_point2(self.end);
-
 		let transform = mod_gradient
 			.to_cols_array()
 			.iter()
@@ -101,35 +171,304 @@ impl Gradient {
 			.map(|(i, entry)| entry.to_string() + if i == 5 { "" } else { "," })
 			.collect::<String>();
 
-		match self.gradient_type {
-			GradientType::Linear => {
+		match self.geometry {
+			GradientGeometry::Linear { start, end } => {
+				let start = mod_points.transform_point2(start);
+				let end = mod_points.transform_point2(end);
 				let _ = write!(
 					svg_defs,
-					r#"<linearGradient id="{}" x1="{}" x2="{}" y1="{}" y2="{}" gradientTransform="matrix({})">{}</linearGradient>"#,
-					self.uuid, start.x, end.x, start.y, end.y, transform, positions
+					r#"<linearGradient id="{}" x1="{}" x2="{}" y1="{}" y2="{}" gradientTransform="matrix({})" spreadMethod="{}">{}</linearGradient>"#,
+					self.uuid, start.x, end.x, start.y, end.y, transform, self.spread, positions
 				);
 			}
-			GradientType::Radial => {
-				let radius = (f64::powi(start.x - end.x, 2) + f64::powi(start.y - end.y, 2)).sqrt();
+			GradientGeometry::Radial { center, radius, focus, focus_radius } => {
+				// `radius`/`focus_radius` are scalars in the untransformed local space `center`/`focus` were stored in, so
+				// they must be carried through `mod_points` too (as a transformed vector's length) to stay in the same
+				// coordinate space as the transformed center/focus points below.
+				let transformed_center = mod_points.transform_point2(center);
+				let transformed_focus = mod_points.transform_point2(focus);
+				let radius = (mod_points.transform_point2(center + DVec2::new(radius, 0.)) - transformed_center).length();
+				let focus_radius = (mod_points.transform_point2(focus + DVec2::new(focus_radius, 0.)) - transformed_focus).length();
 				let _ = write!(
 					svg_defs,
-					r#"<radialGradient id="{}" cx="{}" cy="{}" r="{}" gradientTransform="matrix({})">{}</radialGradient>"#,
-					self.uuid, start.x, start.y, radius, transform, positions
+					r#"<radialGradient id="{}" cx="{}" cy="{}" r="{}" fx="{}" fy="{}" fr="{}" gradientTransform="matrix({})" spreadMethod="{}">{}</radialGradient>"#,
+					self.uuid, transformed_center.x, transformed_center.y, radius, transformed_focus.x, transformed_focus.y, focus_radius, transform, self.spread, positions
 				);
 			}
+			GradientGeometry::Conic { center, start_angle, end_angle } => {
+				// SVG has no native conic gradient element, so approximate it with a `userSpaceOnUse` pattern containing
+				// a fan of narrow wedges. Unlike the gradient elements above, this pattern gets none of `gradientUnits`'s
+				// implicit objectBoundingBox rescale, so the wedges must be computed directly in the same bounds-relative
+				// userSpaceOnUse frame as the pattern tile's own x/y/width/height — via `bound_transform` alone — rather
+				// than through `mod_points`, which folds in `multiplied_transform` and would leave them in a different space.
+				let center = bound_transform.transform_point2(center);
+				let corners = [bounds[0], DVec2::new(bounds[1].x, bounds[0].y), DVec2::new(bounds[0].x, bounds[1].y), bounds[1]];
+				let radius = corners.into_iter().map(|corner| (corner - center).length()).fold(0., f64::max);
+				let wedges = conic_wedges(self, center, start_angle, end_angle, radius);
+				let _ = write!(
+					svg_defs,
+					r#"<pattern id="{}" patternUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}">{}</pattern>"#,
+					self.uuid,
+					bounds[0].x,
+					bounds[0].y,
+					(bounds[1] - bounds[0]).x,
+					(bounds[1] - bounds[0]).y,
+					wedges
+				);
+			}
+		}
+	}
+
+	/// Evaluate the gradient's interpolated color at the parametric position `t`, clamped to `[0, 1]`.
+	///
+	/// # Example
+	/// ```
+	/// # use graphite_graphene::layers::style::{Gradient, GradientType};
+	/// # use graphite_graphene::color::Color;
+	/// # use glam::{DAffine2, DVec2};
+	/// let gradient = Gradient::new(DVec2::ZERO, Color::BLACK, DVec2::ONE, Color::WHITE, DAffine2::IDENTITY, 0, GradientType::Linear);
+	///
+	/// assert_eq!(gradient.evaluate(0.), Color::BLACK);
+	/// assert_eq!(gradient.evaluate(1.), Color::WHITE);
+	/// ```
+	pub fn evaluate(&self, t: f64) -> Color {
+		let t = if t.is_nan() { 0. } else { t.clamp(0., 1.) };
+		let mut stops: Vec<(f64, Color)> = self.positions.iter().filter_map(|&(position, color)| color.map(|color| (position, color))).collect();
+		stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+		match stops.len() {
+			0 => Color::BLACK,
+			1 => stops[0].1,
+			_ if t <= stops[0].0 => stops[0].1,
+			_ if t >= stops[stops.len() - 1].0 => stops[stops.len() - 1].1,
+			_ => {
+				let index = stops.windows(2).position(|window| t >= window[0].0 && t <= window[1].0).unwrap();
+				let (p0, c0) = stops[index];
+				let (p1, c1) = stops[index + 1];
+				let f = if (p1 - p0).abs() > f64::EPSILON { (t - p0) / (p1 - p0) } else { 0. };
+
+				Color::from_rgbaf32(
+					c0.r() + (c1.r() - c0.r()) * f as f32,
+					c0.g() + (c1.g() - c0.g()) * f as f32,
+					c0.b() + (c1.b() - c0.b()) * f as f32,
+					c0.a() + (c1.a() - c0.a()) * f as f32,
+				)
+				.unwrap_or(Color::BLACK)
+			}
+		}
+	}
+}
+
+// Before the `GradientGeometry` refactor, a gradient's geometry was stored as flat `start`/`end`/angle fields directly on
+// `Gradient`. This manual impl migrates documents saved in that shape into the current `geometry` field.
+impl<'de> Deserialize<'de> for Gradient {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum GradientFormat {
+			Current {
+				geometry: GradientGeometry,
+				transform: DAffine2,
+				positions: Vec<(f64, Option<Color>)>,
+				uuid: u64,
+				gradient_type: GradientType,
+				#[serde(default)]
+				spread: SpreadMethod,
+			},
+			Legacy {
+				start: DVec2,
+				end: DVec2,
+				#[serde(default)]
+				start_angle: f64,
+				#[serde(default = "default_legacy_end_angle")]
+				end_angle: f64,
+				transform: DAffine2,
+				positions: Vec<(f64, Option<Color>)>,
+				uuid: u64,
+				gradient_type: GradientType,
+				#[serde(default)]
+				spread: SpreadMethod,
+			},
+		}
+
+		fn default_legacy_end_angle() -> f64 {
+			std::f64::consts::TAU
+		}
+
+		Ok(match GradientFormat::deserialize(deserializer)? {
+			GradientFormat::Current {
+				geometry,
+				transform,
+				positions,
+				uuid,
+				gradient_type,
+				spread,
+			} => Gradient {
+				geometry,
+				transform,
+				positions,
+				uuid,
+				gradient_type,
+				spread,
+			},
+			GradientFormat::Legacy {
+				start,
+				end,
+				start_angle,
+				end_angle,
+				transform,
+				positions,
+				uuid,
+				gradient_type,
+				spread,
+			} => {
+				let geometry = match gradient_type {
+					GradientType::Linear => GradientGeometry::Linear { start, end },
+					GradientType::Radial => GradientGeometry::Radial {
+						center: start,
+						radius: (end - start).length(),
+						focus: start,
+						focus_radius: 0.,
+					},
+					GradientType::Conic => GradientGeometry::Conic { center: start, start_angle, end_angle },
+				};
+
+				Gradient {
+					geometry,
+					transform,
+					positions,
+					uuid,
+					gradient_type,
+					spread,
+				}
+			}
+		})
+	}
+}
+
+/// Builds the fan of narrow triangular wedges used to approximate a [GradientType::Conic] sweep, since SVG has no native conic gradient element.
+fn conic_wedges(gradient: &Gradient, center: DVec2, start_angle: f64, end_angle: f64, radius: f64) -> String {
+	// Subdivide the angular span more finely when there are more color stops to interpolate between.
+	let stop_count = gradient.positions.iter().filter(|(_, color)| color.is_some()).count().max(1);
+	let segment_count = (stop_count * 32).clamp(64, 256);
+	let angle_span = end_angle - start_angle;
+
+	(0..segment_count)
+		.map(|segment| {
+			let t0 = segment as f64 / segment_count as f64;
+			let t1 = (segment + 1) as f64 / segment_count as f64;
+			let angle0 = start_angle + angle_span * t0;
+			let angle1 = start_angle + angle_span * t1;
+			let color = gradient.evaluate((t0 + t1) / 2.);
+			let point0 = center + DVec2::new(angle0.cos(), angle0.sin()) * radius;
+			let point1 = center + DVec2::new(angle1.cos(), angle1.sin()) * radius;
+
+			format!(
+				r##"<path d="M{},{} L{},{} L{},{} Z" fill="#{}" />"##,
+				center.x,
+				center.y,
+				point0.x,
+				point0.y,
+				point1.x,
+				point1.y,
+				color.rgb_hex()
+			)
+		})
+		.collect()
+}
+
+/// How a [Fill::Pattern]'s image repeats to cover its fill region, following the `Image` brush's extend modes in Vello's encoder.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Serialize, Deserialize)]
+pub enum PatternTiling {
+	/// The image repeats across both axes to tile the fill region.
+	Tile,
+	/// The image is drawn once at its natural size with no repetition.
+	None,
+}
+
+impl Default for PatternTiling {
+	fn default() -> Self {
+		PatternTiling::Tile
+	}
+}
+
+/// An image fill, referencing raw (already-encoded, e.g. PNG) image bytes tiled across the filled region according to `tiling`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternFill {
+	image_data: Vec<u8>,
+	mime: String,
+	width: f64,
+	height: f64,
+	pub tiling: PatternTiling,
+	pub transform: DAffine2,
+	uuid: u64,
+}
+
+impl PatternFill {
+	/// Constructs a new pattern fill from raw, already-encoded image bytes (e.g. the contents of a PNG file).
+	pub fn new(image_data: Vec<u8>, mime: impl Into<String>, width: f64, height: f64, transform: DAffine2, uuid: u64) -> Self {
+		Self {
+			image_data,
+			mime: mime.into(),
+			width,
+			height,
+			tiling: PatternTiling::default(),
+			transform,
+			uuid,
 		}
 	}
+
+	/// Adds the pattern def with the uuid specified
+	fn render_defs(&self, svg_defs: &mut String, multiplied_transform: DAffine2, bounds: [DVec2; 2], _transformed_bounds: [DVec2; 2]) {
+		// Unlike `Gradient`'s coordinates (which are normalized to the shape's bounding box), `width`/`height` below are
+		// already absolute pixel dimensions, so only the translation to `bounds[0]` is taken from the bounding box —
+		// folding in its scale too would double-count units and make the tile grow/shrink with the shape's bbox size.
+		let bound_translation = DAffine2::from_translation(bounds[0]);
+
+		// Unlike `Gradient::transform` (which `Gradient::render_defs` leaves unwired), `self.transform` here positions/rotates
+		// the image within the filled region, since a pattern's content has no other way to express that placement.
+		let pattern_transform = multiplied_transform * bound_translation * self.transform;
+
+		let transform = pattern_transform
+			.to_cols_array()
+			.iter()
+			.enumerate()
+			.map(|(i, entry)| entry.to_string() + if i == 5 { "" } else { "," })
+			.collect::<String>();
+
+		let (width, height) = match self.tiling {
+			PatternTiling::Tile => (self.width, self.height),
+			PatternTiling::None => ((bounds[1].x - bounds[0].x).max(self.width), (bounds[1].y - bounds[0].y).max(self.height)),
+		};
+
+		let _ = write!(
+			svg_defs,
+			r#"<pattern id="{}" patternUnits="userSpaceOnUse" width="{}" height="{}" patternTransform="matrix({})"><image href="data:{};base64,{}" width="{}" height="{}" /></pattern>"#,
+			self.uuid,
+			width,
+			height,
+			transform,
+			self.mime,
+			base64::engine::general_purpose::STANDARD.encode(&self.image_data),
+			self.width,
+			self.height
+		);
+	}
 }
 
 /// Describes the fill of a layer.
 ///
-/// Can be None, a solid [Color], a linear [Gradient], a radial [Gradient] or potentially some sort of image or pattern in the future
+/// Can be None, a solid [Color], a linear, radial or conic [Gradient], or an image/[PatternFill].
 #[repr(C)]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Fill {
 	None,
 	Solid(Color),
 	Gradient(Gradient),
+	Pattern(PatternFill),
 }
 
 impl Default for Fill {
@@ -144,13 +483,13 @@ impl Fill {
 		Self::Solid(color)
 	}
 
-	/// Evaluate the color at some point on the fill. Doesn't currently work for Gradient.
+	/// Evaluate the color at some point on the fill. For a gradient, this is its midpoint color. For a pattern, this is a neutral placeholder since the image isn't decoded here.
 	pub fn color(&self) -> Color {
 		match self {
 			Self::None => Color::BLACK,
 			Self::Solid(color) => *color,
-			// TODO: Should correctly sample the gradient
-			Self::Gradient(Gradient { positions, .. }) => positions[0].1.unwrap_or(Color::BLACK),
+			Self::Gradient(gradient) => gradient.evaluate(0.5),
+			Self::Pattern(_) => Color::from_rgbaf32(0.5, 0.5, 0.5, 1.).unwrap_or(Color::BLACK),
 		}
 	}
 
@@ -163,6 +502,10 @@ impl Fill {
 				gradient.render_defs(svg_defs, multiplied_transform, bounds, transformed_bounds);
 				format!(r##" fill="url('#{}')""##, gradient.uuid)
 			}
+			Self::Pattern(pattern) => {
+				pattern.render_defs(svg_defs, multiplied_transform, bounds, transformed_bounds);
+				format!(r##" fill="url('#{}')""##, pattern.uuid)
+			}
 		}
 	}
 
@@ -347,16 +690,63 @@ impl Default for Stroke {
 	}
 }
 
+/// A compositing mode controlling how a layer's rendered pixels blend with the content beneath it, matching the
+/// separable and non-separable Porter-Duff/CSS blend modes that Vello's `BlendMode` enumerates.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlendMode {
+	Normal,
+	Multiply,
+	Screen,
+	Overlay,
+	Darken,
+	Lighten,
+	ColorDodge,
+	ColorBurn,
+	HardLight,
+	SoftLight,
+	Difference,
+	Exclusion,
+	Hue,
+	Saturation,
+	Color,
+	Luminosity,
+}
+
+impl Display for BlendMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			BlendMode::Normal => "normal",
+			BlendMode::Multiply => "multiply",
+			BlendMode::Screen => "screen",
+			BlendMode::Overlay => "overlay",
+			BlendMode::Darken => "darken",
+			BlendMode::Lighten => "lighten",
+			BlendMode::ColorDodge => "color-dodge",
+			BlendMode::ColorBurn => "color-burn",
+			BlendMode::HardLight => "hard-light",
+			BlendMode::SoftLight => "soft-light",
+			BlendMode::Difference => "difference",
+			BlendMode::Exclusion => "exclusion",
+			BlendMode::Hue => "hue",
+			BlendMode::Saturation => "saturation",
+			BlendMode::Color => "color",
+			BlendMode::Luminosity => "luminosity",
+		})
+	}
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct PathStyle {
 	stroke: Option<Stroke>,
 	fill: Fill,
+	blend_mode: Option<BlendMode>,
 }
 
 impl PathStyle {
 	pub fn new(stroke: Option<Stroke>, fill: Fill) -> Self {
-		Self { stroke, fill }
+		Self { stroke, fill, blend_mode: None }
 	}
 
 	/// Get the current path's [Fill].
@@ -463,6 +853,50 @@ impl PathStyle {
 		self.stroke = None;
 	}
 
+	/// Get the current path's [BlendMode].
+	///
+	/// # Example
+	/// ```
+	/// # use graphite_graphene::layers::style::{BlendMode, PathStyle};
+	/// let style = PathStyle::default().with_blend_mode(BlendMode::Multiply);
+	///
+	/// assert_eq!(style.blend_mode(), Some(BlendMode::Multiply));
+	/// ```
+	pub fn blend_mode(&self) -> Option<BlendMode> {
+		self.blend_mode
+	}
+
+	/// Replace the path's [BlendMode] with a provided one.
+	///
+	/// # Example
+	/// ```
+	/// # use graphite_graphene::layers::style::{BlendMode, PathStyle};
+	/// let mut style = PathStyle::default();
+	///
+	/// assert_eq!(style.blend_mode(), None);
+	///
+	/// style.set_blend_mode(BlendMode::Screen);
+	///
+	/// assert_eq!(style.blend_mode(), Some(BlendMode::Screen));
+	/// ```
+	pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+		self.blend_mode = Some(blend_mode);
+	}
+
+	/// Set the path's [BlendMode], consuming and returning `self` for chaining.
+	///
+	/// # Example
+	/// ```
+	/// # use graphite_graphene::layers::style::{BlendMode, PathStyle};
+	/// let style = PathStyle::default().with_blend_mode(BlendMode::Overlay);
+	///
+	/// assert_eq!(style.blend_mode(), Some(BlendMode::Overlay));
+	/// ```
+	pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+		self.blend_mode = Some(blend_mode);
+		self
+	}
+
 	pub fn render(&self, view_mode: ViewMode, svg_defs: &mut String, multiplied_transform: DAffine2, bounds: [DVec2; 2], transformed_bounds: [DVec2; 2]) -> String {
 		let fill_attribute = match (view_mode, &self.fill) {
 			(ViewMode::Outline, _) => Fill::None.render(svg_defs, multiplied_transform, bounds, transformed_bounds),
@@ -473,7 +907,12 @@ impl PathStyle {
 			(_, Some(stroke)) => stroke.render(),
 			(_, None) => String::new(),
 		};
+		let blend_mode_attribute = match (view_mode, self.blend_mode) {
+			(ViewMode::Outline, _) => String::new(),
+			(_, Some(blend_mode)) => format!(r#" style="mix-blend-mode:{}""#, blend_mode),
+			(_, None) => String::new(),
+		};
 
-		format!("{}{}", fill_attribute, stroke_attribute)
+		format!("{}{}{}", fill_attribute, stroke_attribute, blend_mode_attribute)
 	}
 }